@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_openai::types::{ChatCompletionRequestSystemMessageArgs, CreateChatCompletionRequest};
+use serde::Deserialize;
+use strfmt::strfmt;
+use warp::hyper::Body;
+use warp::reply::Response;
+
+use crate::{error_response, sse_response, ApiError, Context};
+
+/// Body of `POST /v1/chat/completions`: the standard OpenAI wire format,
+/// plus an optional `style` field mirroring `/richprompt`'s `style` for
+/// callers that can't set the `x-style` header.
+#[derive(Deserialize)]
+pub struct ChatCompletionsReq {
+    #[serde(flatten)]
+    pub inner: CreateChatCompletionRequest,
+    pub style: Option<String>,
+}
+
+/// OpenAI-compatible `/v1/chat/completions`. Prepends the configured style
+/// system prompt to whatever messages the caller sent and forwards through
+/// the same client pool `/richprompt` uses, so any OpenAI SDK pointed at
+/// this service transparently gets the style injected.
+pub async fn chat_completions(
+    ctx: Arc<Context>,
+    style_header: Option<String>,
+    req: ChatCompletionsReq,
+) -> Response {
+    let style = req.style.or(style_header);
+
+    let build = async {
+        let mut request = req.inner;
+
+        if !ctx.allowed_models.contains(&request.model) {
+            return Err(ApiError::BadRequest(anyhow!(
+                "model '{}' is not in the allowed list",
+                request.model
+            )));
+        }
+        let max_tokens = request
+            .max_tokens
+            .unwrap_or(ctx.max_output_tokens)
+            .min(ctx.max_output_tokens);
+        request.max_tokens = Some(max_tokens);
+
+        let system = match style {
+            None => ctx.system_template.clone(),
+            Some(style) => strfmt!(&ctx.system_with_style_template, style)
+                .map_err(|_| anyhow!("failed to format system_with_style_template"))?,
+        };
+        let system_message = ChatCompletionRequestSystemMessageArgs::default()
+            .content(system)
+            .build()?
+            .into();
+        request.messages.insert(0, system_message);
+
+        Result::<_, ApiError>::Ok(request)
+    };
+
+    let request = match build.await {
+        Ok(request) => request,
+        Err(e) => return error_response(e),
+    };
+
+    let stream = request.stream.unwrap_or(false);
+
+    if stream {
+        match ctx.pool().create_stream(&request).await {
+            Ok(upstream) => sse_response(upstream),
+            Err(e) => error_response(e.into()),
+        }
+    } else {
+        match ctx.pool().create(&request).await {
+            Ok(response) => match serde_json::to_vec(&response) {
+                Ok(body) => {
+                    let mut resp = Response::new(Body::from(body));
+                    resp.headers_mut().insert(
+                        warp::http::header::CONTENT_TYPE,
+                        warp::http::HeaderValue::from_static("application/json"),
+                    );
+                    resp
+                }
+                Err(e) => error_response(e.into()),
+            },
+            Err(e) => error_response(e.into()),
+        }
+    }
+}
@@ -1,7 +1,12 @@
 #[macro_use]
 extern crate log;
 
+mod compat;
+mod pool;
+mod providers;
+
 use std::borrow::Cow;
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
@@ -9,20 +14,16 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
-use async_openai::{
-    Client,
-    types::{
-        ChatCompletionRequestSystemMessageArgs,
-        ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
-    },
+use async_openai::types::{
+    ChatCompletionRequestSystemMessageArgs,
+    ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
 };
-use async_openai::config::OpenAIConfig;
 use clap::Parser;
+use futures_util::StreamExt;
 use log4rs::append::console::ConsoleAppender;
 use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log::LevelFilter;
-use rand::Rng;
 use serde::Deserialize;
 use strfmt::strfmt;
 use warp::Filter;
@@ -30,6 +31,9 @@ use warp::http::StatusCode;
 use warp::hyper::Body;
 use warp::reply::Response;
 
+use pool::ClientPool;
+use providers::ClientConfigEntry;
+
 #[derive(Parser)]
 #[command(version)]
 struct Args {
@@ -41,13 +45,25 @@ struct Config {
     bind_addr: SocketAddr,
     system_template: String,
     system_with_style_template: String,
-    api_keys: Vec<String>,
+    clients: Vec<ClientConfigEntry>,
+    default_model: String,
+    allowed_models: HashSet<String>,
+    max_output_tokens: u16,
 }
 
-struct Context {
-    openai_clients: Vec<Client<OpenAIConfig>>,
-    system_template: String,
-    system_with_style_template: String,
+pub(crate) struct Context {
+    pool: ClientPool,
+    pub(crate) system_template: String,
+    pub(crate) system_with_style_template: String,
+    default_model: String,
+    pub(crate) allowed_models: HashSet<String>,
+    pub(crate) max_output_tokens: u16,
+}
+
+impl Context {
+    pub(crate) fn pool(&self) -> &ClientPool {
+        &self.pool
+    }
 }
 
 fn with_context(
@@ -59,19 +75,91 @@ fn with_context(
 #[derive(Deserialize)]
 struct RichPromptReq {
     prompt: String,
-    style: Option<String>
+    style: Option<String>,
+    #[serde(default)]
+    stream: bool,
+    model: Option<String>,
+    max_tokens: Option<u16>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+}
+
+/// Distinguishes caller mistakes (bad request body, disallowed model) from
+/// genuine backend failures, so the two don't both surface as a 500.
+pub(crate) enum ApiError {
+    BadRequest(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+impl<E> From<E> for ApiError
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(e: E) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+pub(crate) fn error_response(e: ApiError) -> Response {
+    let (status, message) = match e {
+        ApiError::BadRequest(e) => (StatusCode::BAD_REQUEST, e.to_string()),
+        ApiError::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let mut resp = Response::new(Body::from(message));
+    *resp.status_mut() = status;
+    resp
+}
+
+/// Relay a chat completion stream to the caller as `text/event-stream`
+/// frames, terminated by the conventional `data: [DONE]` sentinel.
+pub(crate) fn sse_response(upstream: async_openai::types::ChatCompletionResponseStream) -> Response {
+    let sse = upstream
+        .map(|chunk| {
+            let frame = match chunk {
+                Ok(chunk) => match serde_json::to_string(&chunk) {
+                    Ok(json) => format!("data: {json}\n\n"),
+                    Err(e) => format!("data: {}\n\n", serde_json::json!({"error": e.to_string()})),
+                },
+                Err(e) => format!("data: {}\n\n", serde_json::json!({"error": e.to_string()})),
+            };
+            Result::<_, std::convert::Infallible>::Ok(frame)
+        })
+        .chain(futures_util::stream::once(async {
+            Ok("data: [DONE]\n\n".to_string())
+        }));
+
+    let mut resp = Response::new(Body::wrap_stream(sse));
+    resp.headers_mut().insert(
+        warp::http::header::CONTENT_TYPE,
+        warp::http::HeaderValue::from_static("text/event-stream"),
+    );
+    resp.headers_mut().insert(
+        warp::http::header::CACHE_CONTROL,
+        warp::http::HeaderValue::from_static("no-cache"),
+    );
+    resp
 }
 
 async fn rich_prompt(ctx: Arc<Context>, req: RichPromptReq) -> Response {
-    let fut = async {
+    let stream = req.stream;
+
+    let build = async {
+        let model = req.model.clone().unwrap_or_else(|| ctx.default_model.clone());
+        if !ctx.allowed_models.contains(&model) {
+            return Err(ApiError::BadRequest(anyhow!("model '{model}' is not in the allowed list")));
+        }
+        let max_tokens = req.max_tokens.unwrap_or(ctx.max_output_tokens).min(ctx.max_output_tokens);
+        let top_p = req.top_p.unwrap_or(0.0);
+
         let system = match req.style {
             None => Cow::Borrowed(ctx.system_template.as_str()),
             Some(style) => Cow::Owned(strfmt!(&ctx.system_with_style_template, style).map_err(|_| anyhow!("failed to format system_with_style_template"))?)
         };
 
-        let request = CreateChatCompletionRequestArgs::default()
-            .max_tokens(512u16)
-            .model("gpt-3.5-turbo")
+        let mut builder = CreateChatCompletionRequestArgs::default();
+        builder
+            .max_tokens(max_tokens)
+            .model(model)
             .messages([
                 ChatCompletionRequestSystemMessageArgs::default()
                     .content(system)
@@ -82,27 +170,36 @@ async fn rich_prompt(ctx: Arc<Context>, req: RichPromptReq) -> Response {
                     .build()?
                     .into(),
             ])
-            .top_p(0.0)
-            .build()?;
+            .top_p(top_p)
+            .stream(stream);
+        if let Some(temperature) = req.temperature {
+            builder.temperature(temperature);
+        }
 
-        let clients = ctx.openai_clients.as_slice();
-        let i = rand::thread_rng().gen_range(0..clients.len());
-        let client = &clients[i];
+        Result::<_, ApiError>::Ok(builder.build()?)
+    };
 
-        let mut response = client.chat().create(request).await?;
-        let choice = response.choices.pop().ok_or_else(|| anyhow!("choices is empty"))?;
-        let content = choice.message.content.ok_or_else(|| anyhow!("content is empty"))?;
-        Result::<_, anyhow::Error>::Ok(content)
+    let request = match build.await {
+        Ok(request) => request,
+        Err(e) => return error_response(e),
     };
 
-    match fut.await {
-        Ok(content) => {
-            Response::new(Body::from(content))
+    if stream {
+        match ctx.pool().create_stream(&request).await {
+            Ok(upstream) => sse_response(upstream),
+            Err(e) => error_response(e.into()),
         }
-        Err(e) => {
-            let mut resp = Response::new(Body::from(e.to_string()));
-            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-            return resp;
+    } else {
+        let fut = async {
+            let mut response = ctx.pool().create(&request).await?;
+            let choice = response.choices.pop().ok_or_else(|| anyhow!("choices is empty"))?;
+            let content = choice.message.content.ok_or_else(|| anyhow!("content is empty"))?;
+            Result::<_, ApiError>::Ok(content)
+        };
+
+        match fut.await {
+            Ok(content) => Response::new(Body::from(content)),
+            Err(e) => error_response(e),
         }
     }
 }
@@ -135,31 +232,38 @@ fn logger_init() -> Result<()> {
 async fn serve(config: &Path) -> Result<()> {
     let c = tokio::fs::read_to_string(config).await?;
     let config: Config = toml::from_str(&c)?;
-    let client = reqwest::Client::new();
-
-    let mut clients = Vec::with_capacity(config.api_keys.len());
 
-    for x in &config.api_keys {
-        let open_ai_config = OpenAIConfig::new().with_api_key(x);
-        let open_ai_client = Client::with_config(open_ai_config).with_http_client(client.clone());
-        clients.push(open_ai_client);
-    }
+    let clients = config
+        .clients
+        .iter()
+        .map(|entry| entry.build())
+        .collect::<Result<Vec<_>>>()?;
 
     let ctx = Context {
-        openai_clients: clients,
+        pool: ClientPool::new(clients),
         system_template: config.system_template,
-        system_with_style_template: config.system_with_style_template
+        system_with_style_template: config.system_with_style_template,
+        default_model: config.default_model,
+        allowed_models: config.allowed_models,
+        max_output_tokens: config.max_output_tokens,
     };
 
     let ctx = Arc::new(ctx);
 
     let rich_prompt = warp::path!("richprompt")
         .and(warp::post())
-        .and(with_context(ctx))
+        .and(with_context(ctx.clone()))
         .and(warp::body::json())
         .then(rich_prompt);
 
-    let router = rich_prompt;
+    let chat_completions = warp::path!("v1" / "chat" / "completions")
+        .and(warp::post())
+        .and(with_context(ctx))
+        .and(warp::header::optional::<String>("x-style"))
+        .and(warp::body::json())
+        .then(compat::chat_completions);
+
+    let router = rich_prompt.or(chat_completions);
     let serve = warp::serve(router);
     info!("Listening on http://{}", config.bind_addr);
     serve.bind(config.bind_addr).await;
@@ -0,0 +1,180 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_openai::error::OpenAIError;
+use async_openai::types::{
+    ChatCompletionResponseStream, CreateChatCompletionRequest, CreateChatCompletionResponse,
+};
+use rand::Rng;
+
+use crate::providers::ChatClient;
+
+/// Calls made across the rotation (the first try included) before giving up
+/// and returning the last error.
+const MAX_ATTEMPTS: usize = 3;
+/// Base of the exponential backoff applied between retryable failures.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+/// Ceiling the backoff is capped at regardless of attempt count.
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+/// Consecutive failures before a client is temporarily skipped.
+const FAILURE_THRESHOLD: u32 = 3;
+/// How long a tripped client is skipped for once it hits the threshold.
+const COOLDOWN: Duration = Duration::from_secs(60);
+
+struct ClientState {
+    consecutive_failures: u32,
+    cooldown_until: Option<Instant>,
+}
+
+struct PooledClient {
+    client: Box<dyn ChatClient>,
+    state: Mutex<ClientState>,
+}
+
+/// A set of upstream clients that are load-spread across and failed-over
+/// between, instead of a single random pick that errors out the request.
+pub struct ClientPool {
+    clients: Vec<PooledClient>,
+}
+
+impl ClientPool {
+    pub fn new(clients: Vec<Box<dyn ChatClient>>) -> Self {
+        let clients = clients
+            .into_iter()
+            .map(|client| PooledClient {
+                client,
+                state: Mutex::new(ClientState {
+                    consecutive_failures: 0,
+                    cooldown_until: None,
+                }),
+            })
+            .collect();
+        ClientPool { clients }
+    }
+
+    pub async fn create(
+        &self,
+        request: &CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, OpenAIError> {
+        self.with_failover(|client| client.create(request.clone()))
+            .await
+    }
+
+    pub async fn create_stream(
+        &self,
+        request: &CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionResponseStream, OpenAIError> {
+        self.with_failover(|client| client.create_stream(request.clone()))
+            .await
+    }
+
+    async fn with_failover<F, Fut, T>(&self, mut call: F) -> Result<T, OpenAIError>
+    where
+        F: FnMut(&dyn ChatClient) -> Fut,
+        Fut: Future<Output = Result<T, OpenAIError>>,
+    {
+        if self.clients.is_empty() {
+            return Err(OpenAIError::InvalidArgument("no clients configured".to_string()));
+        }
+
+        let start = rand::thread_rng().gen_range(0..self.clients.len());
+        let mut last_err = None;
+        let mut attempts = 0;
+
+        // Walk the whole rotation looking for a non-cooling-down client, but
+        // only the actual calls we make count against `MAX_ATTEMPTS` -
+        // skipping a tripped client must not burn a retry that a healthy
+        // client elsewhere in the pool could have used.
+        for offset in 0..self.clients.len() {
+            if attempts >= MAX_ATTEMPTS {
+                break;
+            }
+
+            let i = (start + offset) % self.clients.len();
+            let pooled = &self.clients[i];
+
+            if self.is_cooling_down(pooled) {
+                continue;
+            }
+
+            attempts += 1;
+            match call(pooled.client.as_ref()).await {
+                Ok(value) => {
+                    self.record_success(pooled);
+                    info!("request served by client index {i}");
+                    return Ok(value);
+                }
+                Err(e) => {
+                    let retryable = is_retryable(&e);
+                    self.record_failure(pooled);
+                    warn!("client index {i} failed (retryable={retryable}): {e}");
+                    if !retryable {
+                        return Err(e);
+                    }
+                    last_err = Some(e);
+
+                    if attempts < MAX_ATTEMPTS {
+                        tokio::time::sleep(backoff(attempts - 1)).await;
+                    }
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| OpenAIError::InvalidArgument("all clients are cooling down".to_string())))
+    }
+
+    fn is_cooling_down(&self, pooled: &PooledClient) -> bool {
+        let state = pooled.state.lock().unwrap();
+        matches!(state.cooldown_until, Some(until) if Instant::now() < until)
+    }
+
+    fn record_success(&self, pooled: &PooledClient) {
+        let mut state = pooled.state.lock().unwrap();
+        state.consecutive_failures = 0;
+        state.cooldown_until = None;
+    }
+
+    fn record_failure(&self, pooled: &PooledClient) {
+        let mut state = pooled.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= FAILURE_THRESHOLD {
+            state.cooldown_until = Some(Instant::now() + COOLDOWN);
+        }
+    }
+}
+
+/// Exponential backoff with full jitter, doubling from `BASE_BACKOFF` and
+/// capped at `MAX_BACKOFF`.
+fn backoff(attempt: usize) -> Duration {
+    let exp = BASE_BACKOFF.saturating_mul(1 << attempt).min(MAX_BACKOFF);
+    let jittered_ms = rand::thread_rng().gen_range(0..=exp.as_millis() as u64);
+    Duration::from_millis(jittered_ms)
+}
+
+/// Whether an upstream error is worth retrying against another client:
+/// rate limits, server errors, and transport failures are, bad credentials
+/// and similar permanent failures are not.
+///
+/// A plain HTTP 429/5xx comes back from `async-openai` as an `ApiError`
+/// whose `code` is often `null` (e.g. OpenAI's own 500 body is
+/// `{"message": "The server had an error...", "type": "server_error",
+/// "code": null}`) - the `type` field, not `code`, is what actually carries
+/// that signal, so it has to be checked too.
+fn is_retryable(err: &OpenAIError) -> bool {
+    match err {
+        OpenAIError::Reqwest(_) => true,
+        OpenAIError::ApiError(api_err) => {
+            let code = api_err.code.as_deref().unwrap_or_default();
+            let error_type = api_err.r#type.as_deref().unwrap_or_default();
+            let message = api_err.message.to_lowercase();
+            code.contains("rate_limit")
+                || error_type.contains("server_error")
+                || error_type.contains("rate_limit")
+                || message.contains("rate limit")
+                || message.contains("overloaded")
+                || message.contains("server had an error")
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_openai::config::{AzureConfig, Config, OpenAIConfig};
+use async_openai::error::OpenAIError;
+use async_openai::types::{
+    ChatCompletionResponseStream, CreateChatCompletionRequest, CreateChatCompletionResponse,
+};
+use async_openai::Client;
+use async_trait::async_trait;
+use reqwest::Proxy;
+use serde::Deserialize;
+
+/// A single upstream chat backend, abstracted over the wire config
+/// (`OpenAIConfig`, `AzureConfig`, ...) so callers only deal with one type.
+#[async_trait]
+pub trait ChatClient: Send + Sync {
+    async fn create(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, OpenAIError>;
+
+    async fn create_stream(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionResponseStream, OpenAIError>;
+}
+
+#[async_trait]
+impl<C: Config + Send + Sync> ChatClient for Client<C> {
+    async fn create(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> Result<CreateChatCompletionResponse, OpenAIError> {
+        self.chat().create(request).await
+    }
+
+    async fn create_stream(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> Result<ChatCompletionResponseStream, OpenAIError> {
+        self.chat().create_stream(request).await
+    }
+}
+
+/// One entry of the `clients` list in the config TOML. Tagged by `type` so
+/// new providers can be added without touching the request path.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ClientConfigEntry {
+    Openai(OpenAIClientConfig),
+    AzureOpenai(AzureClientConfig),
+    OpenaiCompatible(OpenAICompatibleClientConfig),
+}
+
+/// Transport knobs shared by every provider: which proxy to dial out
+/// through and how long to wait for a connection before giving up.
+#[derive(Deserialize)]
+pub struct TransportConfig {
+    pub proxy: Option<String>,
+    pub connect_timeout: Option<u64>,
+}
+
+impl TransportConfig {
+    /// Build a dedicated `reqwest::Client` for this entry. Falls back to
+    /// `HTTPS_PROXY`/`ALL_PROXY` when no explicit proxy is configured.
+    fn build_http_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::ClientBuilder::new();
+
+        let proxy = self
+            .proxy
+            .clone()
+            .or_else(|| std::env::var("HTTPS_PROXY").ok())
+            .or_else(|| std::env::var("ALL_PROXY").ok());
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(connect_timeout));
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OpenAIClientConfig {
+    pub api_key: String,
+    pub organization_id: Option<String>,
+    #[serde(flatten)]
+    pub transport: TransportConfig,
+}
+
+#[derive(Deserialize)]
+pub struct AzureClientConfig {
+    pub api_key: String,
+    pub api_base: String,
+    pub deployment: String,
+    #[serde(default = "default_azure_api_version")]
+    pub api_version: String,
+    #[serde(flatten)]
+    pub transport: TransportConfig,
+}
+
+fn default_azure_api_version() -> String {
+    "2023-05-15".to_string()
+}
+
+#[derive(Deserialize)]
+pub struct OpenAICompatibleClientConfig {
+    pub api_key: String,
+    pub api_base: String,
+    pub organization_id: Option<String>,
+    #[serde(flatten)]
+    pub transport: TransportConfig,
+}
+
+impl ClientConfigEntry {
+    /// Build the trait object this entry describes, including a dedicated
+    /// `reqwest` client sized to its own proxy/timeout settings.
+    pub fn build(&self) -> Result<Box<dyn ChatClient>> {
+        Ok(match self {
+            ClientConfigEntry::Openai(c) => {
+                let http_client = c.transport.build_http_client()?;
+                let mut config = OpenAIConfig::new().with_api_key(&c.api_key);
+                if let Some(org) = &c.organization_id {
+                    config = config.with_org_id(org);
+                }
+                Box::new(Client::with_config(config).with_http_client(http_client))
+            }
+            ClientConfigEntry::AzureOpenai(c) => {
+                let http_client = c.transport.build_http_client()?;
+                let config = AzureConfig::new()
+                    .with_api_key(&c.api_key)
+                    .with_api_base(&c.api_base)
+                    .with_deployment_id(&c.deployment)
+                    .with_api_version(&c.api_version);
+                Box::new(Client::with_config(config).with_http_client(http_client))
+            }
+            ClientConfigEntry::OpenaiCompatible(c) => {
+                let http_client = c.transport.build_http_client()?;
+                let mut config = OpenAIConfig::new()
+                    .with_api_key(&c.api_key)
+                    .with_api_base(&c.api_base);
+                if let Some(org) = &c.organization_id {
+                    config = config.with_org_id(org);
+                }
+                Box::new(Client::with_config(config).with_http_client(http_client))
+            }
+        })
+    }
+}